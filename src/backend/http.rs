@@ -0,0 +1,121 @@
+//! HTTP backends talking to the physical arena over blocking `reqwest`,
+//! exactly as the original un-abstracted `main` did.
+
+use super::{CarActuator, Frame, FrameSource, Oracle};
+use crate::Quadrant;
+use anyhow::{bail, Context, Result};
+use log::debug;
+use opencv::{core::Vector, imgcodecs};
+use reqwest::blocking::Client;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct DriveCmd {
+    speed: f32,
+    flip: bool,
+}
+
+/// Fetches JPEG frames from a single arena camera over HTTP and decodes them.
+pub struct HttpFrameSource {
+    client: Client,
+    url: &'static str,
+    auth: &'static str,
+}
+
+impl HttpFrameSource {
+    pub fn new(client: Client, url: &'static str, auth: &'static str) -> Self {
+        Self { client, url, auth }
+    }
+}
+
+impl FrameSource for HttpFrameSource {
+    fn fetch(&mut self) -> Result<Frame> {
+        let bytes = self
+            .client
+            .get(self.url)
+            .header("Authorization", self.auth)
+            .send()?
+            .error_for_status()?
+            .bytes()?;
+
+        let buf: Vector<u8> = Vector::from_iter(bytes.iter().copied());
+        let img = imgcodecs::imdecode(&buf, imgcodecs::IMREAD_COLOR)?;
+        if img.empty() {
+            bail!("imdecode returned empty Mat (bad JPEG?)");
+        }
+        Ok(Frame::Image(img))
+    }
+}
+
+/// Polls the arena oracle over HTTP for the current target quadrant.
+pub struct HttpOracle {
+    client: Client,
+    url: &'static str,
+    auth: &'static str,
+}
+
+impl HttpOracle {
+    pub fn new(client: Client, url: &'static str, auth: &'static str) -> Self {
+        Self { client, url, auth }
+    }
+}
+
+impl Oracle for HttpOracle {
+    fn query(&mut self) -> Result<Quadrant> {
+        if let Some(oracle) = option_env!("ORACLE") {
+            return Ok(Quadrant::parse(oracle).unwrap());
+        }
+
+        let body = self
+            .client
+            .get(self.url)
+            .header("Authorization", self.auth)
+            .send()?
+            .error_for_status()?
+            .text()?;
+
+        debug!("Oracle raw response: {body:?}");
+
+        // Handle JSON string, JSON object with "quadrant"/"target" key, or plain text.
+        let raw = if let Ok(s) = serde_json::from_str::<String>(&body) {
+            s
+        } else if let Ok(v) = serde_json::from_str::<serde_json::Value>(&body) {
+            v.get("quadrant")
+                .or_else(|| v.get("target"))
+                .and_then(|x| x.as_str())
+                .unwrap_or(body.trim())
+                .to_string()
+        } else {
+            body.trim().to_string()
+        };
+
+        Quadrant::parse(&raw).with_context(|| format!("unknown quadrant response: {body:?}"))
+    }
+}
+
+/// Drives the car over HTTP by PUTting a `DriveCmd`.
+pub struct HttpCarActuator {
+    client: Client,
+    url: &'static str,
+    auth: &'static str,
+}
+
+impl HttpCarActuator {
+    pub fn new(client: Client, url: &'static str, auth: &'static str) -> Self {
+        Self { client, url, auth }
+    }
+}
+
+impl CarActuator for HttpCarActuator {
+    fn send(&mut self, speed: f32, flip: bool) -> Result<()> {
+        debug!("send_cmd: speed={speed:.2} flip={flip}");
+        self.client
+            .put(self.url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", self.auth)
+            .json(&DriveCmd { speed, flip })
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+//! Backend abstraction over the arena's I/O: camera frames, the oracle's
+//! target-quadrant feed, and the car's drive actuator.
+//!
+//! `main`'s control loop is written only against [`FrameSource`], [`Oracle`]
+//! and [`CarActuator`], so the exact same `steer()`/quadrant-arrival code can
+//! run against the real arena ([`http`]), the in-process simulator
+//! ([`sim`]), or a previously captured session ([`record`]).
+
+pub mod http;
+pub mod record;
+pub mod sim;
+
+use crate::Quadrant;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Detected marker id → (centre_xy, heading_radians), as produced by ArUco
+/// detection or known directly by the simulator.
+pub type DetectedMarkers = HashMap<i32, ((f64, f64), f64)>;
+
+/// One camera's view of the arena for a single tick.
+pub enum Frame {
+    /// A raw decoded image still awaiting ArUco detection.
+    Image(opencv::core::Mat),
+    /// Marker positions the source already knows, paired with the pixel/world
+    /// dimensions they're expressed in. The simulator has no reason to
+    /// rasterize markers it's tracking as ground truth, so it returns this
+    /// directly instead of a synthetic image.
+    Markers {
+        items: DetectedMarkers,
+        dims: (f64, f64),
+    },
+}
+
+/// A source of camera frames. `Send` so a backend can be driven from its own
+/// dedicated thread.
+pub trait FrameSource: Send {
+    fn fetch(&mut self) -> Result<Frame>;
+}
+
+/// The arena oracle: which quadrant the car should currently drive to.
+pub trait Oracle: Send {
+    fn query(&mut self) -> Result<Quadrant>;
+}
+
+/// The car's drive actuator.
+pub trait CarActuator: Send {
+    fn send(&mut self, speed: f32, flip: bool) -> Result<()>;
+}
@@ -0,0 +1,280 @@
+//! Tee/replay backend: wraps a real [`FrameSource`]/[`Oracle`] to log every
+//! response to disk as it's fetched ([`RecordingFrameSource`],
+//! [`RecordingOracle`]), and matching readers that replay a logged session
+//! back through the exact control code with no network or camera I/O at all
+//! ([`ReplayFrameSource`], [`ReplayOracle`]).
+
+use super::{CarActuator, DetectedMarkers, Frame, FrameSource, Oracle};
+use crate::Quadrant;
+use anyhow::{Context, Result};
+use log::debug;
+use opencv::{core::Vector, imgcodecs};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
+enum FrameLogEntry {
+    Image { millis: u128, path: String },
+    Markers {
+        millis: u128,
+        items: DetectedMarkers,
+        dims: (f64, f64),
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct OracleLogEntry {
+    millis: u128,
+    quadrant: String,
+}
+
+/// Wraps any [`FrameSource`] and appends every fetched frame to `dir/frames.jsonl`,
+/// storing images as sibling `frame_NNNNNN.jpg` files. Forwards the real
+/// frame through unchanged.
+pub struct RecordingFrameSource<F> {
+    inner: F,
+    dir: PathBuf,
+    log: File,
+    seq: u64,
+    started: Instant,
+}
+
+impl<F: FrameSource> RecordingFrameSource<F> {
+    pub fn new(inner: F, dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let log = File::create(dir.join("frames.jsonl"))?;
+        Ok(Self {
+            inner,
+            dir,
+            log,
+            seq: 0,
+            started: Instant::now(),
+        })
+    }
+}
+
+impl<F: FrameSource> FrameSource for RecordingFrameSource<F> {
+    fn fetch(&mut self) -> Result<Frame> {
+        let frame = self.inner.fetch()?;
+        let millis = self.started.elapsed().as_millis();
+
+        let entry = match &frame {
+            Frame::Image(mat) => {
+                let mut buf: Vector<u8> = Vector::new();
+                imgcodecs::imencode(".jpg", mat, &mut buf, &Vector::new())?;
+                let name = format!("frame_{:06}.jpg", self.seq);
+                std::fs::write(self.dir.join(&name), buf.as_slice())?;
+                FrameLogEntry::Image { millis, path: name }
+            }
+            Frame::Markers { items, dims } => FrameLogEntry::Markers {
+                millis,
+                items: items.clone(),
+                dims: *dims,
+            },
+        };
+        self.seq += 1;
+        writeln!(self.log, "{}", serde_json::to_string(&entry)?)?;
+
+        Ok(frame)
+    }
+}
+
+/// Replays a [`RecordingFrameSource`] log back as a [`FrameSource`], one
+/// entry per `fetch()` call. Timing between entries is not reproduced — only
+/// the frame sequence and content, which is all the control loop consumes.
+pub struct ReplayFrameSource {
+    dir: PathBuf,
+    lines: std::vec::IntoIter<String>,
+}
+
+impl ReplayFrameSource {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let file = File::open(dir.join("frames.jsonl")).context("open frames.jsonl")?;
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            dir,
+            lines: lines.into_iter(),
+        })
+    }
+}
+
+impl FrameSource for ReplayFrameSource {
+    fn fetch(&mut self) -> Result<Frame> {
+        let line = self.lines.next().context("replay log exhausted")?;
+        let entry: FrameLogEntry = serde_json::from_str(&line)?;
+        Ok(match entry {
+            FrameLogEntry::Image { path, .. } => {
+                let full = self.dir.join(path);
+                let img = imgcodecs::imread(
+                    full.to_str().context("non-UTF8 replay path")?,
+                    imgcodecs::IMREAD_COLOR,
+                )?;
+                Frame::Image(img)
+            }
+            FrameLogEntry::Markers { items, dims, .. } => Frame::Markers { items, dims },
+        })
+    }
+}
+
+/// Wraps any [`Oracle`] and appends every response to `dir/oracle.jsonl`.
+/// Forwards the real response through unchanged.
+pub struct RecordingOracle<O> {
+    inner: O,
+    log: File,
+    started: Instant,
+}
+
+impl<O: Oracle> RecordingOracle<O> {
+    pub fn new(inner: O, dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let log = File::create(dir.join("oracle.jsonl"))?;
+        Ok(Self {
+            inner,
+            log,
+            started: Instant::now(),
+        })
+    }
+}
+
+impl<O: Oracle> Oracle for RecordingOracle<O> {
+    fn query(&mut self) -> Result<Quadrant> {
+        let q = self.inner.query()?;
+        let entry = OracleLogEntry {
+            millis: self.started.elapsed().as_millis(),
+            quadrant: q.code().to_string(),
+        };
+        writeln!(self.log, "{}", serde_json::to_string(&entry)?)?;
+        Ok(q)
+    }
+}
+
+/// Replays a [`RecordingOracle`] log back as an [`Oracle`], one entry per
+/// `query()` call, holding the last entry once the log is exhausted (an
+/// oracle poll that never changes again is a realistic end-of-session state).
+pub struct ReplayOracle {
+    lines: std::vec::IntoIter<String>,
+    last: Option<Quadrant>,
+}
+
+impl ReplayOracle {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(dir.as_ref().join("oracle.jsonl")).context("open oracle.jsonl")?;
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            lines: lines.into_iter(),
+            last: None,
+        })
+    }
+}
+
+impl Oracle for ReplayOracle {
+    fn query(&mut self) -> Result<Quadrant> {
+        let Some(line) = self.lines.next() else {
+            return self.last.context("replay oracle log exhausted with no prior entry");
+        };
+        let entry: OracleLogEntry = serde_json::from_str(&line)?;
+        let q = Quadrant::parse(&entry.quadrant)
+            .with_context(|| format!("unknown quadrant in replay log: {:?}", entry.quadrant))?;
+        self.last = Some(q);
+        Ok(q)
+    }
+}
+
+/// No-op actuator for replay sessions, where there is no physical car to
+/// drive: logs the command that would have been sent and discards it.
+pub struct NullCarActuator;
+
+impl CarActuator for NullCarActuator {
+    fn send(&mut self, speed: f32, flip: bool) -> Result<()> {
+        debug!("replay: would send speed={speed:.2} flip={flip}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed in-memory sequence of marker frames, standing in for a real
+    /// camera so the recording/replay round trip can be tested with no
+    /// network or ArUco detection involved.
+    struct FixedFrames(std::vec::IntoIter<DetectedMarkers>);
+
+    impl FrameSource for FixedFrames {
+        fn fetch(&mut self) -> Result<Frame> {
+            let items = self.0.next().context("fixed frames exhausted")?;
+            Ok(Frame::Markers { items, dims: (100.0, 100.0) })
+        }
+    }
+
+    /// A fixed in-memory sequence of oracle responses.
+    struct FixedOracle(std::vec::IntoIter<Quadrant>);
+
+    impl Oracle for FixedOracle {
+        fn query(&mut self) -> Result<Quadrant> {
+            self.0.next().context("fixed oracle exhausted")
+        }
+    }
+
+    /// A fresh, empty scratch directory under the system temp dir, unique to
+    /// this test process so parallel test runs don't collide.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "hs-rust-nation-record-test-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    #[test]
+    fn frame_record_replay_round_trip() {
+        let dir = scratch_dir("frames");
+        let mut markers = DetectedMarkers::new();
+        markers.insert(9, ((12.0, 34.0), 0.7));
+
+        let source = FixedFrames(vec![markers.clone()].into_iter());
+        let mut recording = RecordingFrameSource::new(source, dir.clone()).unwrap();
+        let recorded = recording.fetch().unwrap();
+        let Frame::Markers { items, .. } = recorded else {
+            panic!("expected a markers frame");
+        };
+        assert_eq!(items, markers);
+
+        let mut replay = ReplayFrameSource::open(dir.clone()).unwrap();
+        let replayed = replay.fetch().unwrap();
+        let Frame::Markers { items, .. } = replayed else {
+            panic!("expected a markers frame");
+        };
+        assert_eq!(items, markers);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn oracle_record_replay_round_trip() {
+        let dir = scratch_dir("oracle");
+
+        let source = FixedOracle(vec![Quadrant::TopRight].into_iter());
+        let mut recording = RecordingOracle::new(source, &dir).unwrap();
+        assert_eq!(recording.query().unwrap(), Quadrant::TopRight);
+
+        let mut replay = ReplayOracle::open(&dir).unwrap();
+        assert_eq!(replay.query().unwrap(), Quadrant::TopRight);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
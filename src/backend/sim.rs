@@ -0,0 +1,148 @@
+//! In-process arena simulator: a 2D car pose integrated from the last drive
+//! command, served as already-detected marker positions plus a scripted
+//! oracle feed. Lets `steer()` and the quadrant-arrival logic run and be
+//! tested end-to-end deterministically, with no physical arena involved.
+
+use super::{CarActuator, DetectedMarkers, Frame, FrameSource, Oracle};
+use crate::Quadrant;
+use anyhow::Result;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const CAR_MARKER_ID: i32 = 9;
+
+/// World dimensions the simulator reports alongside its marker positions.
+pub const SIM_DIMS: (f64, f64) = (1280.0, 720.0);
+
+/// Forward speed (world px/s) at `DriveCmd::speed == 1.0`.
+const SIM_MAX_SPEED: f64 = 220.0;
+/// Turn rate (rad/s) at `DriveCmd::speed == 1.0` while spinning in place.
+const SIM_MAX_TURN_RATE: f64 = 3.0;
+
+struct Pose {
+    x: f64,
+    y: f64,
+    heading: f64,
+}
+
+/// Simulated arena state shared by every [`SimFrameSource`]/[`SimOracle`]/
+/// [`SimCarActuator`] in a session: the car's pose, the last command applied
+/// to it, the four fixed quadrant-marker positions, and a scripted oracle
+/// sequence.
+pub struct SimWorld {
+    pose: Pose,
+    cmd: (f32, bool),
+    last_tick: Instant,
+    corners: DetectedMarkers,
+    script: Vec<(Duration, Quadrant)>,
+    started: Instant,
+}
+
+impl SimWorld {
+    /// `script` is a list of `(elapsed_since_start, quadrant)` the oracle
+    /// switches to, in ascending-time order; the oracle holds the last
+    /// reached entry once the script is exhausted.
+    pub fn new(start_pose: (f64, f64, f64), script: Vec<(Duration, Quadrant)>) -> Self {
+        let now = Instant::now();
+        let mut corners = DetectedMarkers::new();
+        corners.insert(13, ((100.0, 100.0), 0.0));
+        corners.insert(11, ((SIM_DIMS.0 - 100.0, 100.0), 0.0));
+        corners.insert(14, ((100.0, SIM_DIMS.1 - 100.0), 0.0));
+        corners.insert(12, ((SIM_DIMS.0 - 100.0, SIM_DIMS.1 - 100.0), 0.0));
+
+        Self {
+            pose: Pose {
+                x: start_pose.0,
+                y: start_pose.1,
+                heading: start_pose.2,
+            },
+            cmd: (0.0, false),
+            last_tick: now,
+            corners,
+            script,
+            started: now,
+        }
+    }
+
+    /// Backdates `last_tick` so the next `advance()` integrates a fixed
+    /// `dt` instead of whatever real wall-clock time has elapsed, so tests
+    /// driving the simulator don't depend on how fast the test happens to
+    /// run.
+    #[cfg(test)]
+    pub(crate) fn force_tick(&mut self, dt: Duration) {
+        self.last_tick = Instant::now() - dt;
+    }
+
+    /// Integrate the pose forward by the time elapsed since the last call.
+    fn advance(&mut self) {
+        let dt = self.last_tick.elapsed().as_secs_f64();
+        self.last_tick = Instant::now();
+
+        let (speed, flip) = self.cmd;
+        if flip {
+            // Model a car whose turn direction `crate::TURN_POLARITY` is
+            // tuned for, so `steer()`'s spin corrects the heading error.
+            self.pose.heading +=
+                crate::TURN_POLARITY as f64 * speed as f64 * SIM_MAX_TURN_RATE * dt;
+        } else {
+            self.pose.x += speed as f64 * SIM_MAX_SPEED * dt * self.pose.heading.cos();
+            self.pose.y += speed as f64 * SIM_MAX_SPEED * dt * self.pose.heading.sin();
+        }
+    }
+
+    fn markers(&mut self) -> DetectedMarkers {
+        self.advance();
+        let mut items = self.corners.clone();
+        items.insert(CAR_MARKER_ID, ((self.pose.x, self.pose.y), self.pose.heading));
+        items
+    }
+
+    fn oracle_target(&self) -> Quadrant {
+        let elapsed = self.started.elapsed();
+        self.script
+            .iter()
+            .rev()
+            .find(|(at, _)| elapsed >= *at)
+            .map(|(_, q)| *q)
+            .unwrap_or(Quadrant::TopLeft)
+    }
+}
+
+/// `FrameSource` over a shared [`SimWorld`]. Every camera in a sim session
+/// wraps the same world, so all cameras see identical ground truth; swap in
+/// per-camera noise/offsets here if that stops being realistic enough.
+/// `Arc<Mutex<_>>` rather than `Rc<RefCell<_>>` since each camera's source
+/// runs on its own fetch thread.
+pub struct SimFrameSource(pub Arc<Mutex<SimWorld>>);
+
+impl FrameSource for SimFrameSource {
+    fn fetch(&mut self) -> Result<Frame> {
+        let items = self.0.lock().unwrap().markers();
+        Ok(Frame::Markers {
+            items,
+            dims: SIM_DIMS,
+        })
+    }
+}
+
+/// `Oracle` over a shared [`SimWorld`]'s scripted quadrant sequence.
+pub struct SimOracle(pub Arc<Mutex<SimWorld>>);
+
+impl Oracle for SimOracle {
+    fn query(&mut self) -> Result<Quadrant> {
+        Ok(self.0.lock().unwrap().oracle_target())
+    }
+}
+
+/// `CarActuator` that just records the last command into a shared
+/// [`SimWorld`] for the next `advance()` to integrate.
+pub struct SimCarActuator(pub Arc<Mutex<SimWorld>>);
+
+impl CarActuator for SimCarActuator {
+    fn send(&mut self, speed: f32, flip: bool) -> Result<()> {
+        self.0.lock().unwrap().cmd = (speed, flip);
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Fuses the two cameras' independent pixel coordinate systems into one
+//! arena world frame via a per-camera homography, keyed off the four fixed
+//! quadrant corner markers (13=TL, 11=TR, 14=BL, 12=BR).
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use opencv::{
+    core::{self, Mat, Point2f, Vector},
+    imgproc,
+    prelude::*,
+};
+
+use crate::backend::DetectedMarkers;
+
+/// Known physical arena dimensions, in the world units all fused positions,
+/// the arrival radius and the PID distances are now expressed in.
+pub(crate) const ARENA_WIDTH: f64 = 2000.0;
+pub(crate) const ARENA_HEIGHT: f64 = 1200.0;
+
+/// Maps a quadrant corner marker id to its known physical arena position.
+/// Returns `None` for any other marker id (e.g. the car itself).
+pub(crate) fn corner_world_pos(marker_id: i32) -> Option<(f64, f64)> {
+    match marker_id {
+        13 => Some((0.0, 0.0)),
+        11 => Some((ARENA_WIDTH, 0.0)),
+        14 => Some((0.0, ARENA_HEIGHT)),
+        12 => Some((ARENA_WIDTH, ARENA_HEIGHT)),
+        _ => None,
+    }
+}
+
+type CornerMap = HashMap<i32, (f64, f64)>;
+
+/// A single camera's cached pixel→world homography, recomputed only when
+/// its corner marker detections actually move.
+pub(crate) struct Homography {
+    h: Option<Mat>,
+    last_corners: CornerMap,
+}
+
+impl Homography {
+    pub(crate) fn new() -> Self {
+        Self {
+            h: None,
+            last_corners: CornerMap::new(),
+        }
+    }
+
+    /// Warps `point` and its `heading` (this camera's pixel space) into
+    /// arena world units, recomputing the homography first if all four
+    /// corners are visible and have moved since the last estimate. Returns
+    /// `None` until the camera has seen all four corners at least once.
+    ///
+    /// The homography is a perspective map with rotation and (generally
+    /// non-uniform) scale, so an angle measured in pixel space isn't the
+    /// same bearing in world space — the heading is warped by projecting a
+    /// second point one unit ahead along it and taking the world-frame
+    /// `atan2` between the two warped points, rather than passed through.
+    pub(crate) fn to_world(
+        &mut self,
+        items: &DetectedMarkers,
+        point: (f64, f64),
+        heading: f64,
+    ) -> Result<Option<((f64, f64), f64)>> {
+        let corners: CornerMap = items
+            .iter()
+            .filter_map(|(&id, &(pos, _))| corner_world_pos(id).map(|_| (id, pos)))
+            .collect();
+
+        if corners.len() == 4 && corners_moved(&self.last_corners, &corners) {
+            self.h = Some(estimate_homography(&corners)?);
+            self.last_corners = corners;
+        }
+
+        let Some(h) = &self.h else {
+            return Ok(None);
+        };
+
+        let world_pos = warp_point(h, point)?;
+        let ahead = (point.0 + heading.cos(), point.1 + heading.sin());
+        let world_ahead = warp_point(h, ahead)?;
+        let world_heading = (world_ahead.1 - world_pos.1).atan2(world_ahead.0 - world_pos.0);
+
+        Ok(Some((world_pos, world_heading)))
+    }
+}
+
+/// Pixel jitter (px) below which we don't bother re-estimating the
+/// homography — ArUco centroid noise shouldn't cause constant recompute.
+const CORNER_MOVE_EPS: f64 = 1.5;
+
+fn corners_moved(old: &CornerMap, new: &CornerMap) -> bool {
+    new.iter().any(|(id, &(x, y))| match old.get(id) {
+        Some(&(ox, oy)) => (x - ox).hypot(y - oy) > CORNER_MOVE_EPS,
+        None => true,
+    })
+}
+
+/// Fits the 3×3 perspective transform mapping this camera's four detected
+/// corner-marker centres to their known world positions. We always have
+/// exactly 4 correspondences (one per quadrant marker), so an exact solve is
+/// the right tool; `calib3d::find_homography` with RANSAC would only help if
+/// we fed it redundant points (e.g. each marker's own four corners), which
+/// isn't worth the complexity here.
+fn estimate_homography(corners: &CornerMap) -> Result<Mat> {
+    let mut src: Vector<Point2f> = Vector::new();
+    let mut dst: Vector<Point2f> = Vector::new();
+    for (&id, &(x, y)) in corners {
+        let (wx, wy) = corner_world_pos(id).expect("corners pre-filtered to known marker ids");
+        src.push(Point2f::new(x as f32, y as f32));
+        dst.push(Point2f::new(wx as f32, wy as f32));
+    }
+
+    imgproc::get_perspective_transform(&src, &dst, core::DECOMP_LU)
+        .context("get_perspective_transform")
+}
+
+/// Warps `(x, y, 1)` through the 3×3 homography `h` and divides through by
+/// the third component to get the world `(X, Y)`.
+fn warp_point(h: &Mat, (x, y): (f64, f64)) -> Result<(f64, f64)> {
+    let e = |r: i32, c: i32| -> Result<f64> { Ok(*h.at_2d::<f64>(r, c)?) };
+
+    let px = e(0, 0)? * x + e(0, 1)? * y + e(0, 2)?;
+    let py = e(1, 0)? * x + e(1, 1)? * y + e(1, 2)?;
+    let w = e(2, 0)? * x + e(2, 1)? * y + e(2, 2)?;
+
+    Ok((px / w, py / w))
+}
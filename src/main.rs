@@ -1,18 +1,23 @@
+mod backend;
+mod fusion;
+mod vision;
+
 use anyhow::{bail, Context, Result};
-use log::{debug, error, info, warn};
-use opencv::{
-    aruco::{
-        self, get_predefined_dictionary, DetectorParameters, Dictionary, PREDEFINED_DICTIONARY_NAME,
+use backend::{
+    http::{HttpCarActuator, HttpFrameSource, HttpOracle},
+    record::{
+        NullCarActuator, RecordingFrameSource, RecordingOracle, ReplayFrameSource, ReplayOracle,
     },
-    core::{Point2f, Vector},
-    imgcodecs,
-    prelude::*,
+    sim::{SimCarActuator, SimFrameSource, SimOracle, SimWorld},
+    CarActuator, Frame, FrameSource, Oracle,
 };
+use fusion::Homography;
+use log::{debug, error, info, warn};
+use opencv::prelude::*;
 use reqwest::blocking::Client;
-use serde::Serialize;
 use std::{
-    collections::HashMap,
     f64::consts::PI,
+    sync::{mpsc, Arc, Mutex},
     thread,
     time::{Duration, Instant},
 };
@@ -39,16 +44,94 @@ const ANGLE_OK: f64 = 0.50;
 /// Flip to -1.0 if the car turns the wrong direction when angle_err > 0.
 const TURN_POLARITY: f32 = -1.0;
 
+/// Distance (world units, see `fusion::ARENA_WIDTH`/`ARENA_HEIGHT`) within
+/// which the car is considered to have arrived at the target quadrant's
+/// corner. Re-derived from the old ~50px radius on a ~1280px-wide camera
+/// frame, scaled to the 2000-wide arena.
+const ARRIVAL_RADIUS: f64 = 80.0;
+
+// ─── PID gains ────────────────────────────────────────────────────────────────
+// Tuned per car; override here if a replacement chassis needs different gains.
+
+const HEADING_KP: f64 = 1.2;
+const HEADING_KI: f64 = 0.05;
+const HEADING_KD: f64 = 0.15;
+
+const SPEED_KP: f64 = 0.0030;
+const SPEED_KI: f64 = 0.0002;
+const SPEED_KD: f64 = 0.0008;
+
+/// Output clamp for the heading PID (same units as `DriveCmd::speed`).
+const TURN_LIMIT: f64 = 0.6;
+/// Output clamp for the distance/speed PID.
+const SPEED_LIMIT: f64 = 0.85;
+/// Anti-windup clamp on each PID's accumulated integral term.
+const INTEGRAL_LIMIT: f64 = 5.0;
+
+// ─── Event loop timing ─────────────────────────────────────────────────────────
+
+/// How often the oracle is polled on its own background cadence.
+const ORACLE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Delay before a camera thread retries after a failed fetch.
+const CAMERA_RETRY_DELAY: Duration = Duration::from_millis(200);
+/// If no camera frame arrives within this long, command a safe stop instead
+/// of driving on a stale command.
+const FRAME_DEADLINE: Duration = Duration::from_millis(400);
+/// How stale the other camera's last world-position estimate may be and
+/// still be averaged in when both cameras see the car.
+const FUSION_WINDOW: Duration = Duration::from_millis(300);
+
 // ─── Types ────────────────────────────────────────────────────────────────────
 
-#[derive(Serialize)]
 struct DriveCmd {
     speed: f32,
     flip: bool,
 }
 
+/// A textbook PID controller carried across loop iterations, with anti-windup.
+struct Pid {
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    prev_err: f64,
+    integral: f64,
+    last: Instant,
+}
+
+impl Pid {
+    fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            prev_err: 0.0,
+            integral: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Feed a fresh error sample and return the control output, clamped to
+    /// `±limit`. Integration freezes while the output is saturated so the
+    /// integral term can't wind up past what the clamp will ever use.
+    fn update(&mut self, err: f64, limit: f64) -> f64 {
+        let dt = self.last.elapsed().as_secs_f64().max(1e-3);
+        self.last = Instant::now();
+
+        let unsaturated =
+            self.kp * err + self.ki * self.integral + self.kd * (err - self.prev_err) / dt;
+        let output = unsaturated.clamp(-limit, limit);
+
+        if output == unsaturated {
+            self.integral = (self.integral + err * dt).clamp(-INTEGRAL_LIMIT, INTEGRAL_LIMIT);
+        }
+        self.prev_err = err;
+
+        output
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Quadrant {
+pub(crate) enum Quadrant {
     TopLeft,
     TopRight,
     BottomLeft,
@@ -56,8 +139,10 @@ enum Quadrant {
 }
 
 impl Quadrant {
-    fn from_pos(x: f64, y: f64, w: f64, h: f64) -> Self {
-        match (x > w / 2.0, y > h / 2.0) {
+    /// Classifies a world position (`fusion::ARENA_WIDTH`×`ARENA_HEIGHT`
+    /// units) into the quadrant it falls in.
+    fn from_pos(x: f64, y: f64) -> Self {
+        match (x > fusion::ARENA_WIDTH / 2.0, y > fusion::ARENA_HEIGHT / 2.0) {
             (false, false) => Self::TopLeft,
             (true, false) => Self::TopRight,
             (false, true) => Self::BottomLeft,
@@ -65,7 +150,17 @@ impl Quadrant {
         }
     }
 
-    fn parse(s: &str) -> Option<Self> {
+    /// This quadrant's corner marker id, per the fixed arena layout.
+    fn marker_id(&self) -> i32 {
+        match self {
+            Self::TopLeft => 13,
+            Self::TopRight => 11,
+            Self::BottomLeft => 14,
+            Self::BottomRight => 12,
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
         match s.trim().to_ascii_uppercase().as_str() {
             "13" | "TL" | "Q1" | "1" | "TOP_LEFT" => Some(Self::TopLeft),
             "11" | "TR" | "Q2" | "2" | "TOP_RIGHT" => Some(Self::TopRight),
@@ -74,6 +169,177 @@ impl Quadrant {
             _ => None,
         }
     }
+
+    /// Canonical short code, the inverse of [`Quadrant::parse`]. Used to log
+    /// and persist a quadrant in a form that round-trips.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Self::TopLeft => "TL",
+            Self::TopRight => "TR",
+            Self::BottomLeft => "BL",
+            Self::BottomRight => "BR",
+        }
+    }
+}
+
+/// Identifies which camera a [`FrameEvent`] came from, for logging only.
+#[derive(Debug, Clone, Copy)]
+enum CamId {
+    Cam1,
+    Cam2,
+}
+
+/// A frame as it arrives off a camera's dedicated fetch thread.
+struct FrameEvent {
+    cam: CamId,
+    frame: Frame,
+}
+
+/// The I/O backends the control loop drives: two camera frame sources, the
+/// oracle, and the car's actuator.
+struct Io {
+    cam1: Box<dyn FrameSource>,
+    cam2: Box<dyn FrameSource>,
+    oracle: Box<dyn Oracle>,
+    car: Box<dyn CarActuator>,
+}
+
+/// Selects the I/O backend from the environment:
+/// - `SIM=1` drives an in-process [`SimWorld`] instead of the physical arena.
+/// - `REPLAY_DIR=<dir>` replays a session previously captured with `RECORD_DIR`.
+/// - otherwise talks to the real arena over HTTP, optionally teeing every
+///   response to `RECORD_DIR=<dir>` for later replay.
+fn build_io(client: Client) -> Result<Io> {
+    if std::env::var_os("SIM").is_some() {
+        info!("Backend: in-process simulator");
+        let script = vec![
+            (Duration::from_secs(0), Quadrant::TopLeft),
+            (Duration::from_secs(8), Quadrant::TopRight),
+            (Duration::from_secs(16), Quadrant::BottomRight),
+            (Duration::from_secs(24), Quadrant::BottomLeft),
+        ];
+        let world = Arc::new(Mutex::new(SimWorld::new((640.0, 360.0, 0.0), script)));
+        return Ok(Io {
+            cam1: Box::new(SimFrameSource(world.clone())),
+            cam2: Box::new(SimFrameSource(world.clone())),
+            oracle: Box::new(SimOracle(world.clone())),
+            car: Box::new(SimCarActuator(world)),
+        });
+    }
+
+    if let Some(dir) = std::env::var_os("REPLAY_DIR") {
+        let dir = std::path::PathBuf::from(dir);
+        info!("Backend: replaying session from {}", dir.display());
+        return Ok(Io {
+            cam1: Box::new(ReplayFrameSource::open(dir.join("cam1"))?),
+            cam2: Box::new(ReplayFrameSource::open(dir.join("cam2"))?),
+            oracle: Box::new(ReplayOracle::open(dir.join("oracle"))?),
+            car: Box::new(NullCarActuator),
+        });
+    }
+
+    let cam1 = HttpFrameSource::new(client.clone(), CAM1_URL, CAM1_AUTH);
+    let cam2 = HttpFrameSource::new(client.clone(), CAM2_URL, CAM2_AUTH);
+    let oracle = HttpOracle::new(client.clone(), ORACLE_URL, ORACLE_AUTH);
+    let car = HttpCarActuator::new(client, CAR_URL, CAR_AUTH);
+
+    if let Some(dir) = std::env::var_os("RECORD_DIR") {
+        let dir = std::path::PathBuf::from(dir);
+        info!("Backend: HTTP arena, recording session to {}", dir.display());
+        return Ok(Io {
+            cam1: Box::new(RecordingFrameSource::new(cam1, dir.join("cam1"))?),
+            cam2: Box::new(RecordingFrameSource::new(cam2, dir.join("cam2"))?),
+            oracle: Box::new(RecordingOracle::new(oracle, dir.join("oracle"))?),
+            car: Box::new(car),
+        });
+    }
+
+    info!("Backend: HTTP arena");
+    Ok(Io {
+        cam1: Box::new(cam1),
+        cam2: Box::new(cam2),
+        oracle: Box::new(oracle),
+        car: Box::new(car),
+    })
+}
+
+/// Runs `source.fetch()` in a tight loop on its own thread, forwarding every
+/// frame to `tx` as it arrives so a slow camera can never stall the other
+/// camera or the control loop. Exits once the receiving end is dropped.
+fn spawn_camera_thread(
+    cam: CamId,
+    mut source: Box<dyn FrameSource>,
+    tx: mpsc::Sender<FrameEvent>,
+) {
+    thread::spawn(move || loop {
+        match source.fetch() {
+            Ok(frame) => {
+                if tx.send(FrameEvent { cam, frame }).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                error!("{cam:?} fetch failed: {e}");
+                thread::sleep(CAMERA_RETRY_DELAY);
+            }
+        }
+    });
+}
+
+/// Polls the oracle on its own `ORACLE_POLL_INTERVAL` cadence in the
+/// background and publishes the latest target quadrant into `target`.
+fn spawn_oracle_thread(mut oracle: Box<dyn Oracle>, target: Arc<Mutex<Option<Quadrant>>>) {
+    thread::spawn(move || loop {
+        match oracle.query() {
+            Ok(q) => {
+                let mut slot = target.lock().unwrap();
+                if *slot != Some(q) {
+                    info!("🎯 New target quadrant: {:?}", q);
+                }
+                *slot = Some(q);
+            }
+            Err(e) => error!("Oracle poll failed: {e}"),
+        }
+        thread::sleep(ORACLE_POLL_INTERVAL);
+    });
+}
+
+/// Sends `DriveCmd`s received on `rx` to the car. Runs on its own thread so
+/// a slow/blocking actuator call never delays the control loop from
+/// processing the next frame event. If commands queue up faster than the
+/// actuator can send them, drains to the newest before sending so the car
+/// never executes a backlog of stale commands.
+fn spawn_actuator_thread(mut car: Box<dyn CarActuator>, rx: mpsc::Receiver<DriveCmd>) {
+    thread::spawn(move || {
+        while let Ok(mut cmd) = rx.recv() {
+            while let Ok(newer) = rx.try_recv() {
+                cmd = newer;
+            }
+            if let Err(e) = car.send(cmd.speed, cmd.flip) {
+                error!("Drive command failed: {e}");
+            }
+        }
+    });
+}
+
+/// Pulls marker detections out of a frame, running ArUco detection for a raw
+/// image or passing through a source's already-known positions.
+fn extract_markers(frame: Frame, detector: &vision::Detector) -> Result<backend::DetectedMarkers> {
+    match frame {
+        Frame::Image(mat) => {
+            debug!("Frame ({}×{})", mat.cols(), mat.rows());
+            vision::detect_car(detector, &mat)
+        }
+        Frame::Markers { items, .. } => Ok(items),
+    }
+}
+
+/// The car's last fused world-position estimate from one camera, used to
+/// average the two cameras' readings when both see the car within
+/// `FUSION_WINDOW` of each other.
+struct CarEstimate {
+    pos: (f64, f64),
+    at: Instant,
 }
 
 // ─── Main loop ────────────────────────────────────────────────────────────────
@@ -92,274 +358,177 @@ fn main() -> Result<()> {
         .build()
         .context("HTTP client")?;
 
-    let detector = make_detector()?;
+    let Io {
+        cam1,
+        cam2,
+        oracle,
+        car,
+    } = build_io(client)?;
+
+    let detector = vision::make_detector()?;
     info!("ArUco detector ready (DICT_4X4_50, car marker id={CAR_MARKER_ID})");
 
-    let mut tl = None;
-    let mut tr = None;
-    let mut bl = None;
-    let mut br = None;
+    // ── Wire up the background I/O threads ──────────────────────────────────
+    let target = Arc::new(Mutex::new(None::<Quadrant>));
+    spawn_oracle_thread(oracle, target.clone());
+
+    let (frame_tx, frame_rx) = mpsc::channel::<FrameEvent>();
+    spawn_camera_thread(CamId::Cam1, cam1, frame_tx.clone());
+    spawn_camera_thread(CamId::Cam2, cam2, frame_tx);
+
+    let (cmd_tx, cmd_rx) = mpsc::channel::<DriveCmd>();
+    spawn_actuator_thread(car, cmd_rx);
+
+    // ── Control loop: fires on each new frame event ──────────────────────────
+    let mut cam1_homog = Homography::new();
+    let mut cam2_homog = Homography::new();
+    let mut last_est: [Option<CarEstimate>; 2] = [None, None];
 
-    let mut target: Option<Quadrant> = None;
-    let mut last_oracle = Instant::now() - Duration::from_secs(30);
     let mut no_car_count = 0u32;
 
+    let mut heading_pid = Pid::new(HEADING_KP, HEADING_KI, HEADING_KD);
+    let mut speed_pid = Pid::new(SPEED_KP, SPEED_KI, SPEED_KD);
+
     loop {
-        // ── Oracle poll ──────────────────────────────────────────────────────
-        if last_oracle.elapsed() >= Duration::from_secs(2) {
-            match query_oracle(&client) {
-                Ok(q) => {
-                    if target != Some(q) {
-                        info!("🎯 New target quadrant: {:?}", q);
-                        target = Some(q);
-                    } else {
-                        debug!("Oracle: still {:?}", q);
-                    }
-                }
-                Err(e) => error!("Oracle poll failed: {e}"),
+        let mut ev = match frame_rx.recv_timeout(FRAME_DEADLINE) {
+            Ok(ev) => ev,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                warn!("No fresh camera frame within {FRAME_DEADLINE:?} — commanding safe stop");
+                cmd_tx
+                    .send(DriveCmd {
+                        speed: 0.0,
+                        flip: false,
+                    })
+                    .ok();
+                continue;
             }
-            last_oracle = Instant::now();
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                bail!("all camera threads have exited");
+            }
+        };
+        // Detection is slower than frame arrival, so the channel can queue
+        // up faster than we drain it; drop everything but the newest frame
+        // so the control computation never runs on a stale position.
+        while let Ok(newer) = frame_rx.try_recv() {
+            ev = newer;
         }
 
-        let Some(tgt) = target else {
+        let Some(tgt) = *target.lock().unwrap() else {
             debug!("Waiting for first oracle response…");
-            thread::sleep(Duration::from_millis(200));
             continue;
         };
 
-        // ── Camera frame ─────────────────────────────────────────────────────
-        let frame1 = match fetch_frame(&client, CAM1_URL, CAM1_AUTH) {
-            Ok(f) => {
-                debug!("Frame from camera1 ({}×{})", f.cols(), f.rows());
-                Some(f)
-            }
-            Err(e) => {
-                error!("camera 1 failed: {e}");
-                None
-            }
-        };
-        let frame2 = match fetch_frame(&client, CAM2_URL, CAM2_AUTH) {
-            Ok(f) => {
-                debug!("Frame from camera2 ({}×{})", f.cols(), f.rows());
-                Some(f)
-            }
+        let items = match extract_markers(ev.frame, &detector) {
+            Ok(v) => v,
             Err(e) => {
-                error!("camera 2 failed: {e}");
-                None
+                error!("{:?} detection error: {e}", ev.cam);
+                continue;
             }
         };
 
-        let mut car = None;
-        let mut w = None;
-        let mut h = None;
-        for frame in [frame1, frame2].into_iter().flatten() {
-            // ── Detect our car ───────────────────────────────────────────────────
-            let items = match detect_car(&detector, &frame) {
-                Err(e) => {
-                    error!("Detection error: {e}");
-                    thread::sleep(Duration::from_millis(100));
-                    continue;
-                }
-                Ok(items) => items,
-            };
-
-            let frame_car = items.get(&CAR_MARKER_ID).copied();
-
-            let mut found = false;
-
-            if let Some(&pos) = items.get(&13) {
-                debug!("found pos of TopLeft: {pos:?}");
-                tl = Some(pos);
-
-                if tgt == Quadrant::TopLeft {
-                    found = true;
-                }
-            }
-            if let Some(&pos) = items.get(&11) {
-                debug!("found pos of TopRight: {pos:?}");
-                tr = Some(pos);
+        let frame_car = items.get(&CAR_MARKER_ID).copied();
 
-                if tgt == Quadrant::TopRight {
-                    found = true;
-                }
-            }
-            if let Some(&pos) = items.get(&14) {
-                debug!("found pos of BottomLeft: {pos:?}");
-                bl = Some(pos);
+        let homog = match ev.cam {
+            CamId::Cam1 => &mut cam1_homog,
+            CamId::Cam2 => &mut cam2_homog,
+        };
 
-                if tgt == Quadrant::BottomLeft {
-                    found = true;
+        let world = match frame_car {
+            None => None,
+            Some((pixel_pos, heading)) => match homog.to_world(&items, pixel_pos, heading) {
+                Ok(Some((world_pos, world_heading))) => Some((world_pos, world_heading)),
+                Ok(None) => {
+                    debug!("{:?}: homography not ready yet (need all 4 corners)", ev.cam);
+                    None
                 }
-            }
-            if let Some(&pos) = items.get(&12) {
-                debug!("found pos of BottomRight: {pos:?}");
-                br = Some(pos);
-
-                if tgt == Quadrant::BottomRight {
-                    found = true;
+                Err(e) => {
+                    error!("{:?} homography error: {e}", ev.cam);
+                    None
                 }
-            }
-
-            if car.is_none() || (frame_car.is_some() && found) {
-                car = frame_car;
-            }
-
-            if w.is_none() || found {
-                w = Some(frame.cols() as f64);
-            }
-            if h.is_none() || found {
-                h = Some(frame.rows() as f64);
-            }
-        }
-
-        let w = w.unwrap();
-        let h = h.unwrap();
+            },
+        };
 
-        match car {
+        match world {
             None => {
                 no_car_count += 1;
                 warn!("Car marker not found in frame (miss #{no_car_count})");
                 if no_car_count > 3 {
                     debug!("Spinning to find marker…");
-                    send_cmd(&client, 0.45 * TURN_POLARITY, true).ok();
+                    cmd_tx
+                        .send(DriveCmd {
+                            speed: 0.45 * TURN_POLARITY,
+                            flip: true,
+                        })
+                        .ok();
                 }
             }
 
-            Some((pos, heading)) => {
+            Some((world_pos, heading)) => {
                 no_car_count = 0;
-                // let tgt_centre = tgt.centre(w, h);
-                let Some((tgt_centre, _)) = (match tgt {
-                    Quadrant::TopLeft => tl,
-                    Quadrant::TopRight => tr,
-                    Quadrant::BottomLeft => bl,
-                    Quadrant::BottomRight => br,
-                }) else {
-                    error!("Couldn't find target location");
-                    thread::sleep(Duration::from_millis(100));
-                    continue;
+
+                let idx = match ev.cam {
+                    CamId::Cam1 => 0,
+                    CamId::Cam2 => 1,
                 };
+                let other = last_est[1 - idx].as_ref().filter(|o| o.at.elapsed() < FUSION_WINDOW);
+                let pos = match other {
+                    Some(o) => (
+                        (world_pos.0 + o.pos.0) / 2.0,
+                        (world_pos.1 + o.pos.1) / 2.0,
+                    ),
+                    None => world_pos,
+                };
+                last_est[idx] = Some(CarEstimate {
+                    pos: world_pos,
+                    at: Instant::now(),
+                });
+
+                let tgt_centre = fusion::corner_world_pos(tgt.marker_id())
+                    .expect("every Quadrant maps to a known corner marker");
 
-                let car_quad = Quadrant::from_pos(pos.0, pos.1, w, h);
+                let car_quad = Quadrant::from_pos(pos.0, pos.1);
                 let dist = (pos.0 - tgt_centre.0).hypot(pos.1 - tgt_centre.1);
 
-                // if car_quad == tgt {
                 info!("distance {dist} from {tgt:?}");
-                if dist < 50.0 {
+                if dist < ARRIVAL_RADIUS {
                     info!("✅ In target {:?} — holding position", tgt);
-                    send_cmd(&client, 0.0, false).ok();
+                    cmd_tx
+                        .send(DriveCmd {
+                            speed: 0.0,
+                            flip: false,
+                        })
+                        .ok();
                 } else {
-                    let cmd = steer(pos, heading, tgt_centre);
+                    let cmd = steer(&mut heading_pid, &mut speed_pid, pos, heading, tgt_centre);
                     info!(
                         "pos=({:.0},{:.0}) hdg={:.2}rad | {:?}→{:?} | speed={:.2} flip={}",
                         pos.0, pos.1, heading, car_quad, tgt, cmd.speed, cmd.flip
                     );
-                    if let Err(e) = send_cmd(&client, cmd.speed, cmd.flip) {
-                        error!("Drive command failed: {e}");
-                    }
+                    cmd_tx.send(cmd).ok();
                 }
             }
         }
-
-        thread::sleep(Duration::from_millis(100));
     }
 }
 
 // ─── Helpers ──────────────────────────────────────────────────────────────────
 
-struct Detector {
-    dict: opencv::core::Ptr<Dictionary>,
-    params: opencv::core::Ptr<DetectorParameters>,
-}
-
-fn make_detector() -> Result<Detector> {
-    let dict = get_predefined_dictionary(PREDEFINED_DICTIONARY_NAME::DICT_4X4_50)
-        .context("ArUco dictionary")?;
-    let params = DetectorParameters::create()?;
-
-    Ok(Detector { dict, params })
-}
-
-fn fetch_frame(client: &Client, url: &str, auth: &str) -> Result<opencv::core::Mat> {
-    let bytes = client
-        .get(url)
-        .header("Authorization", auth)
-        .send()?
-        .error_for_status()?
-        .bytes()?;
-
-    let buf: Vector<u8> = Vector::from_iter(bytes.iter().copied());
-    let img = imgcodecs::imdecode(&buf, imgcodecs::IMREAD_COLOR)?;
-    if img.empty() {
-        bail!("imdecode returned empty Mat (bad JPEG?)");
-    }
-    Ok(img)
-}
-
-/// Returns (centre_xy, heading_radians) for our car marker, or None if not seen.
-fn detect_car(
-    detector: &Detector,
-    frame: &opencv::core::Mat,
-) -> Result<HashMap<i32, ((f64, f64), f64)>> {
-    let mut corners: Vector<opencv::core::Mat> = Vector::new();
-    let mut ids = opencv::core::Mat::default();
-    let mut rejected: Vector<opencv::core::Mat> = Vector::new();
-
-    let mut items = HashMap::new();
-
-    aruco::detect_markers(
-        frame,
-        &detector.dict,
-        &mut corners,
-        &mut ids,
-        &detector.params,
-        &mut rejected,
-    )?;
-
-    let n = ids.rows();
-    debug!("Detected {n} marker(s) in frame");
-
-    for i in 0..n {
-        let id = *ids.at_2d::<i32>(i, 0)?;
-        debug!("  marker id={id}");
-
-        // corners[i] is a 1×4 Mat of Point2f (TL, TR, BR, BL order)
-        let m = corners.get(i as usize)?;
-        let c0 = *m.at_2d::<Point2f>(0, 0)?; // top-left
-        let c1 = *m.at_2d::<Point2f>(0, 1)?; // top-right
-        let c2 = *m.at_2d::<Point2f>(0, 2)?; // bottom-right
-        let c3 = *m.at_2d::<Point2f>(0, 3)?; // bottom-left
-
-        let cx = (c0.x + c1.x + c2.x + c3.x) as f64 / 4.0;
-        let cy = (c0.y + c1.y + c2.y + c3.y) as f64 / 4.0;
-
-        // Heading: from centre toward mid-point of the top edge (c0→c1).
-        // If the car's physical forward direction differs, adjust TURN_POLARITY
-        // or add a heading offset here.
-        let fx = (c0.x + c1.x) as f64 / 2.0;
-        let fy = (c0.y + c1.y) as f64 / 2.0;
-        let heading = (fy - cy).atan2(fx - cx);
-
-        debug!("Car found: centre=({cx:.1},{cy:.1}) heading={heading:.3}rad");
-        items.insert(id, ((cx, cy), heading));
-    }
-
-    Ok(items)
-}
-
-/// Compute a drive command to steer from `pos`/`hdg` toward `target`.
-fn steer(pos: (f64, f64), hdg: f64, target: (f64, f64)) -> DriveCmd {
+/// Compute a drive command to steer from `pos`/`hdg` toward `target`, driving
+/// `heading_pid` and `speed_pid` one tick each — every call ticks both,
+/// regardless of which regime the car ends up in, so neither's `dt`/`prev_err`
+/// goes stale across calls where the other dominated. Both are stateful
+/// across calls: callers must reuse the same pair every tick for a given car.
+fn steer(
+    heading_pid: &mut Pid,
+    speed_pid: &mut Pid,
+    pos: (f64, f64),
+    hdg: f64,
+    target: (f64, f64),
+) -> DriveCmd {
     let dx = target.0 - pos.0;
     let dy = target.1 - pos.1;
     let dist = (dx * dx + dy * dy).sqrt();
 
-    // if dist < ARRIVE_PX {
-    //     debug!("steer: close enough ({dist:.0}px < {ARRIVE_PX}px) — stop");
-    //     return DriveCmd {
-    //         speed: 0.0,
-    //         flip: false,
-    //     };
-    // }
-
     let desired = dy.atan2(dx);
     let mut err = desired - hdg;
     while err > PI {
@@ -369,63 +538,85 @@ fn steer(pos: (f64, f64), hdg: f64, target: (f64, f64)) -> DriveCmd {
         err += 2.0 * PI;
     }
 
-    debug!("steer: dist={dist:.0}px desired={desired:.2}rad err={err:.2}rad");
+    debug!("steer: dist={dist:.0}wu desired={desired:.2}rad err={err:.2}rad");
 
-    if err.abs() > ANGLE_OK {
-        let spd = TURN_POLARITY * if err > 0.0 { 0.2 } else { -0.2 };
-        debug!("steer: turning (speed={spd:.2}, flip=true)");
-        DriveCmd {
-            speed: spd,
-            flip: true,
-        }
-    } else {
-        let spd = (dist / 300.0).clamp(0.45, 0.85) as f32;
-        debug!("steer: driving forward (speed={spd:.2}, flip=false)");
-        DriveCmd {
-            speed: spd,
-            flip: false,
-        }
+    let turn = heading_pid.update(err, TURN_LIMIT);
+    let fwd = speed_pid.update(dist, SPEED_LIMIT);
+
+    // The actuator is single-DOF (`flip` selects turn-in-place vs
+    // drive-forward; a `DriveCmd` carries only one `speed`), so the two PID
+    // outputs can't be summed into one scalar without one magnitude
+    // swamping the other — select by mode instead. The discontinuity at
+    // the ANGLE_OK boundary is inherent to that hardware; both PIDs still
+    // tick every call so neither goes stale across the switch.
+    let flip = err.abs() > ANGLE_OK;
+    let speed = if flip { TURN_POLARITY as f64 * turn } else { fwd };
+
+    debug!("steer: flip={flip} speed={speed:.2}");
+
+    DriveCmd {
+        speed: speed as f32,
+        flip,
     }
 }
 
-fn query_oracle(client: &Client) -> Result<Quadrant> {
-    if let Some(oracle) = option_env!("ORACLE") {
-        return Ok(Quadrant::parse(oracle).unwrap());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks `world` by a fixed, deterministic `dt` and returns the resulting
+    /// marker frame, bypassing the real-wall-clock `advance()` the live
+    /// camera threads rely on.
+    fn tick(world: &Arc<Mutex<SimWorld>>, dt: Duration) -> backend::DetectedMarkers {
+        let mut w = world.lock().unwrap();
+        w.force_tick(dt);
+        drop(w);
+        match SimFrameSource(world.clone()).fetch().unwrap() {
+            Frame::Markers { items, .. } => items,
+            Frame::Image(_) => panic!("SimFrameSource should only ever emit Frame::Markers"),
+        }
     }
 
-    let body = client
-        .get(ORACLE_URL)
-        .header("Authorization", ORACLE_AUTH)
-        .send()?
-        .error_for_status()?
-        .text()?;
-
-    debug!("Oracle raw response: {body:?}");
-
-    // Handle JSON string, JSON object with "quadrant"/"target" key, or plain text.
-    let raw = if let Ok(s) = serde_json::from_str::<String>(&body) {
-        s
-    } else if let Ok(v) = serde_json::from_str::<serde_json::Value>(&body) {
-        v.get("quadrant")
-            .or_else(|| v.get("target"))
-            .and_then(|x| x.as_str())
-            .unwrap_or(body.trim())
-            .to_string()
-    } else {
-        body.trim().to_string()
-    };
-
-    Quadrant::parse(&raw).with_context(|| format!("unknown quadrant response: {body:?}"))
-}
+    /// Drives a [`SimWorld`] through the exact `steer()`/quadrant-arrival
+    /// logic `main`'s control loop uses, on a fixed simulated timestep, and
+    /// asserts the car reaches the scripted target quadrant.
+    #[test]
+    fn sim_world_reaches_target_quadrant() {
+        let script = vec![(Duration::from_secs(0), Quadrant::BottomRight)];
+        let world = Arc::new(Mutex::new(SimWorld::new((640.0, 360.0, 0.0), script)));
+        let mut car = SimCarActuator(world.clone());
+        let tgt = SimOracle(world.clone()).query().unwrap();
+        let tgt_centre = fusion::corner_world_pos(tgt.marker_id()).unwrap();
+
+        let mut homog = Homography::new();
+        let mut heading_pid = Pid::new(HEADING_KP, HEADING_KI, HEADING_KD);
+        let mut speed_pid = Pid::new(SPEED_KP, SPEED_KI, SPEED_KD);
+
+        let mut arrived = false;
+        for _ in 0..5_000 {
+            let items = tick(&world, Duration::from_millis(20));
+            let (pixel_pos, heading) = items[&CAR_MARKER_ID];
+            let Some((pos, heading)) = homog.to_world(&items, pixel_pos, heading).unwrap() else {
+                continue;
+            };
+
+            let dist = (pos.0 - tgt_centre.0).hypot(pos.1 - tgt_centre.1);
+            if dist < ARRIVAL_RADIUS {
+                arrived = true;
+                break;
+            }
 
-fn send_cmd(client: &Client, speed: f32, flip: bool) -> Result<()> {
-    debug!("send_cmd: speed={speed:.2} flip={flip}");
-    client
-        .put(CAR_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", CAR_AUTH)
-        .json(&DriveCmd { speed, flip })
-        .send()?
-        .error_for_status()?;
-    Ok(())
+            // Backdate both PIDs so their `dt` is the simulated timestep, not
+            // the wall-clock time this loop happens to take.
+            heading_pid.last = Instant::now() - Duration::from_millis(20);
+            speed_pid.last = Instant::now() - Duration::from_millis(20);
+            let cmd = steer(&mut heading_pid, &mut speed_pid, pos, heading, tgt_centre);
+            car.send(cmd.speed, cmd.flip).unwrap();
+        }
+
+        assert!(
+            arrived,
+            "car never reached the target quadrant within the tick budget"
+        );
+    }
 }
@@ -0,0 +1,72 @@
+//! ArUco marker detection over a decoded camera frame.
+
+use crate::backend::DetectedMarkers;
+use anyhow::Result;
+use log::debug;
+use opencv::{
+    aruco::{
+        self, get_predefined_dictionary, DetectorParameters, Dictionary, PREDEFINED_DICTIONARY_NAME,
+    },
+    core::{Point2f, Vector},
+    prelude::*,
+};
+
+pub struct Detector {
+    dict: opencv::core::Ptr<Dictionary>,
+    params: opencv::core::Ptr<DetectorParameters>,
+}
+
+pub fn make_detector() -> Result<Detector> {
+    let dict = get_predefined_dictionary(PREDEFINED_DICTIONARY_NAME::DICT_4X4_50)?;
+    let params = DetectorParameters::create()?;
+
+    Ok(Detector { dict, params })
+}
+
+/// Returns (centre_xy, heading_radians) per detected marker id.
+pub fn detect_car(detector: &Detector, frame: &opencv::core::Mat) -> Result<DetectedMarkers> {
+    let mut corners: Vector<opencv::core::Mat> = Vector::new();
+    let mut ids = opencv::core::Mat::default();
+    let mut rejected: Vector<opencv::core::Mat> = Vector::new();
+
+    let mut items = DetectedMarkers::new();
+
+    aruco::detect_markers(
+        frame,
+        &detector.dict,
+        &mut corners,
+        &mut ids,
+        &detector.params,
+        &mut rejected,
+    )?;
+
+    let n = ids.rows();
+    debug!("Detected {n} marker(s) in frame");
+
+    for i in 0..n {
+        let id = *ids.at_2d::<i32>(i, 0)?;
+        debug!("  marker id={id}");
+
+        // corners[i] is a 1×4 Mat of Point2f (TL, TR, BR, BL order)
+        let m = corners.get(i as usize)?;
+        let c0 = *m.at_2d::<Point2f>(0, 0)?; // top-left
+        let c1 = *m.at_2d::<Point2f>(0, 1)?; // top-right
+        let c2 = *m.at_2d::<Point2f>(0, 2)?; // bottom-right
+        let c3 = *m.at_2d::<Point2f>(0, 3)?; // bottom-left
+
+        let cx = (c0.x + c1.x + c2.x + c3.x) as f64 / 4.0;
+        let cy = (c0.y + c1.y + c2.y + c3.y) as f64 / 4.0;
+
+        // Heading: from centre toward mid-point of the top edge (c0→c1).
+        // If the car's physical forward direction differs, adjust TURN_POLARITY
+        // or add a heading offset here.
+        let fx = (c0.x + c1.x) as f64 / 2.0;
+        let fy = (c0.y + c1.y) as f64 / 2.0;
+        let heading = (fy - cy).atan2(fx - cx);
+
+        debug!("Car found: centre=({cx:.1},{cy:.1}) heading={heading:.3}rad");
+        items.insert(id, ((cx, cy), heading));
+    }
+
+    Ok(items)
+}